@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::hash::Hash;
+
+/// Content-addressed blob cache keyed by the manifest [`Hash`]. Blobs live under
+/// a per-user cache directory in files named by their lowercase hex digest, so a
+/// file already fetched in a previous aborted run is never downloaded twice.
+///
+/// Cross-run reuse is the guarantee here: within a single run `Manifest.files`
+/// is a `HashMap<Hash, PathBuf>`, so two paths sharing a digest collapse to one
+/// entry and only the cache survives to satisfy a later identical digest.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Open (creating if necessary) the blob cache under the per-user cache
+    /// directory.
+    pub fn discover() -> Result<Self, Error> {
+        let dir = dirs::cache_dir()
+            .ok_or(Error::CacheDirNotFound)?
+            .join("golden-forgery")
+            .join("blobs");
+        std::fs::create_dir_all(&dir).map_err(|_| Error::CacheDirNotFound)?;
+        Ok(Self { dir })
+    }
+
+    fn blob_path(&self, hash: &Hash) -> PathBuf {
+        self.dir.join(hash.as_hex())
+    }
+
+    /// If a cached blob for `hash` exists and still hashes to it, return its
+    /// path so the caller can link it into place instead of downloading. The
+    /// rehash is CPU-bound, so it runs on the blocking pool rather than
+    /// stalling the async runtime inside `buffer_unordered`.
+    pub async fn get(&self, hash: &Hash) -> Option<PathBuf> {
+        let blob = self.blob_path(hash);
+        if !blob.exists() {
+            return None;
+        }
+        let expected = hash.clone();
+        let algorithm = hash.algorithm();
+        let rehash_blob = blob.clone();
+        match tokio::task::spawn_blocking(move || Hash::of_file(&rehash_blob, algorithm)).await {
+            Ok(Ok(actual)) if actual == expected => Some(blob),
+            _ => None,
+        }
+    }
+
+    /// Copy a freshly verified file into the cache so later runs can reuse it.
+    /// Failures are non-fatal: the cache is an optimization, not a source of
+    /// truth.
+    pub fn put(&self, hash: &Hash, path: &Path) {
+        let blob = self.blob_path(hash);
+        if blob.exists() {
+            return;
+        }
+        let _ = std::fs::copy(path, &blob);
+    }
+}
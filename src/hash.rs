@@ -1,20 +1,108 @@
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha512};
 
 use crate::Error;
 use std::{path::PathBuf, str::FromStr};
 
+/// The digest algorithm a [`Hash`] was produced with. Manifest entries may tag
+/// their digest with an `algo:` prefix; unqualified digests default to sha256.
+#[derive(std::hash::Hash, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl Algorithm {
+    /// The length, in hex characters, of a digest from this algorithm.
+    fn hex_len(self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Blake3 => 64,
+            Algorithm::Sha512 => 128,
+        }
+    }
+
+    /// The `algo:` prefix this algorithm is written with in a manifest.
+    fn prefix(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Digest `data` with this algorithm, returning the lowercase hex string.
+    fn digest_hex(self, data: &[u8]) -> String {
+        match self {
+            Algorithm::Sha256 => base16ct::lower::encode_string(&Sha256::digest(data)),
+            Algorithm::Sha512 => base16ct::lower::encode_string(&Sha512::digest(data)),
+            Algorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Algorithm::Sha256),
+            "sha512" => Ok(Algorithm::Sha512),
+            "blake3" => Ok(Algorithm::Blake3),
+            _ => Err(Error::InvalidHash),
+        }
+    }
+}
+
 #[derive(std::hash::Hash, Debug, PartialEq, Eq, Clone)]
-pub struct Hash(String);
+pub struct Hash {
+    algorithm: Algorithm,
+    hex: String,
+}
+
+impl Hash {
+    /// The digest as its lowercase hex string, without the algorithm prefix.
+    pub fn as_hex(&self) -> &str {
+        &self.hex
+    }
+
+    /// The algorithm this digest was produced with.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Hash the file at `path` with `algorithm`, dispatching to the matching
+    /// digest implementation.
+    pub fn of_file(path: &PathBuf, algorithm: Algorithm) -> Result<Self, Error> {
+        let data = std::fs::read(path).map_err(|_| crate::Error::FileReadError)?;
+        Ok(Self {
+            algorithm,
+            hex: algorithm.digest_hex(&data),
+        })
+    }
+}
 
 impl FromStr for Hash {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 64 {
+        // An optional `algo:` prefix selects the algorithm; a bare digest is
+        // sha256 for backward compatibility.
+        let (algorithm, hex) = match s.split_once(':') {
+            Some((prefix, rest)) => (Algorithm::from_str(prefix)?, rest),
+            None => (Algorithm::Sha256, s),
+        };
+
+        // Reject anything that isn't genuinely lowercase hex of the expected
+        // width rather than trusting the string blindly.
+        let is_lower_hex = hex.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        if hex.len() != algorithm.hex_len() || !is_lower_hex {
             return Err(Error::InvalidHash);
         }
 
-        Ok(Self(s.to_string()))
+        Ok(Self {
+            algorithm,
+            hex: hex.to_string(),
+        })
     }
 }
 
@@ -22,10 +110,68 @@ impl TryFrom<&PathBuf> for Hash {
     type Error = crate::Error;
 
     fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
-        let data = std::fs::read(path).map_err(|_| crate::Error::FileReadError)?;
-        let hash = Sha256::digest(data);
-        let hexstr = base16ct::lower::encode_string(&hash);
+        Self::of_file(path, Algorithm::Sha256)
+    }
+}
+
+impl std::fmt::Display for Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algorithm.prefix(), self.hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256_HEX: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn bare_digest_defaults_to_sha256() {
+        let hash = Hash::from_str(SHA256_HEX).unwrap();
+        assert_eq!(hash.algorithm(), Algorithm::Sha256);
+        assert_eq!(hash.as_hex(), SHA256_HEX);
+    }
+
+    #[test]
+    fn algo_prefix_selects_algorithm() {
+        let hash = Hash::from_str(&format!("sha256:{SHA256_HEX}")).unwrap();
+        assert_eq!(hash.algorithm(), Algorithm::Sha256);
+        assert_eq!(hash.as_hex(), SHA256_HEX);
+    }
+
+    #[test]
+    fn sha512_requires_its_longer_width() {
+        // A sha256-width digest tagged as sha512 is rejected on length.
+        assert!(Hash::from_str(&format!("sha512:{SHA256_HEX}")).is_err());
+        let long = "a".repeat(128);
+        assert_eq!(
+            Hash::from_str(&format!("sha512:{long}")).unwrap().algorithm(),
+            Algorithm::Sha512
+        );
+    }
+
+    #[test]
+    fn unknown_algorithm_is_rejected() {
+        assert!(Hash::from_str(&format!("md5:{SHA256_HEX}")).is_err());
+    }
+
+    #[test]
+    fn uppercase_and_non_hex_are_rejected() {
+        assert!(Hash::from_str(&SHA256_HEX.to_uppercase()).is_err());
+        let with_g = format!("g{}", &SHA256_HEX[1..]);
+        assert!(Hash::from_str(&with_g).is_err());
+    }
+
+    #[test]
+    fn wrong_length_is_rejected() {
+        assert!(Hash::from_str(&SHA256_HEX[..63]).is_err());
+        assert!(Hash::from_str(&format!("{SHA256_HEX}a")).is_err());
+    }
 
-        Self::from_str(&hexstr)
+    #[test]
+    fn display_round_trips_through_from_str() {
+        let hash = Hash::from_str(SHA256_HEX).unwrap();
+        assert_eq!(Hash::from_str(&hash.to_string()).unwrap(), hash);
     }
 }
@@ -1,14 +1,21 @@
+use std::io::Write as _;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
+use cache::Cache;
+use futures::stream::{self, StreamExt};
+use futures::{SinkExt, Stream};
 use hash::Hash;
 use iced::widget::{center, center_x, column, image, progress_bar, text};
 use iced::{Element, Task};
-use manifest::Manifest;
+use manifest::{FileToDownload, Manifest};
 use octocrab::models::repos::Release;
 use reqwest;
 use tempdir::TempDir;
 use thiserror::Error;
 
+mod cache;
 mod hash;
 mod manifest;
 
@@ -36,54 +43,324 @@ async fn check_update() -> Result<(Release, Manifest), Error> {
     Manifest::from_repository("GoldenForgery", "files").await
 }
 
-async fn compare_files(manifest: Manifest) -> Result<Vec<PathBuf>, Error> {
-    let mut invalid_files = Vec::<PathBuf>::new();
-    for (manifest_hash, path) in manifest.files {
-        // Missing file
-        if !path.exists() {
-            invalid_files.push(path);
-            continue;
+/// Maximum number of files hashed at once while comparing against the manifest.
+const NUMBER_OF_MAX_CONCURRENT_HASHES: usize = 16;
+
+/// Maximum number of per-file downloads in flight at once, so a large invalid
+/// set doesn't open hundreds of sockets.
+const NUMBER_OF_MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+async fn compare_files(manifest: Manifest) -> Result<Vec<FileToDownload>, Error> {
+    // Hashing is CPU-bound and blocks the executor, so each check is spawned on
+    // the blocking pool and the checks are driven concurrently.
+    let checks = manifest.files.iter().map(|(manifest_hash, path)| {
+        let manifest_hash = manifest_hash.clone();
+        let path = path.clone();
+        async move {
+            // A file is invalid if it is missing, unreadable, or its hash differs.
+            let needs_download = if !path.exists() {
+                true
+            } else {
+                let hash_path = path.clone();
+                let algorithm = manifest_hash.algorithm();
+                match tokio::task::spawn_blocking(move || Hash::of_file(&hash_path, algorithm)).await
+                {
+                    Ok(Ok(existing_file_hash)) => existing_file_hash != manifest_hash,
+                    // Join failure or unreadable file: fetch it again to be safe.
+                    _ => true,
+                }
+            };
+            (manifest_hash, path, needs_download)
         }
+    });
+
+    let results: Vec<_> = stream::iter(checks)
+        .buffer_unordered(NUMBER_OF_MAX_CONCURRENT_HASHES)
+        .collect()
+        .await;
+
+    let mut invalid_files = Vec::<FileToDownload>::new();
+    for (manifest_hash, path, needs_download) in results {
+        if needs_download {
+            let url = manifest
+                .url_for(&manifest_hash)
+                .ok_or_else(|| Error::NoDownloadUrl(path.clone()))?;
+            invalid_files.push(FileToDownload {
+                hash: manifest_hash,
+                path,
+                url,
+            });
+        }
+    }
+
+    Ok(invalid_files)
+}
+
+/// Build the stream of progress messages for an update run. Each invalid file
+/// is streamed chunk-by-chunk so the running byte counter can drive the bar,
+/// and a terminal `Finish`/`Err` message is emitted when the run completes.
+fn update(invalid_files: Vec<FileToDownload>) -> impl Stream<Item = Message> {
+    iced::stream::channel(16, move |mut output| async move {
+        let result = run_downloads(invalid_files, &mut output).await;
+        let message = match result {
+            Ok(()) => Message::Finish,
+            Err(err) => Message::Err(err.to_string()),
+        };
+        let _ = output.send(message).await;
+    })
+}
+
+/// Fetch every invalid file with `bytes_stream`, writing each to a temp path
+/// and moving it into place, while pushing incremental progress out `output`.
+/// Only the files that failed validation are fetched, leaving the rest alone.
+/// Downloads run with bounded concurrency and the per-file byte counts are
+/// aggregated into the single overall progress bar via shared counters.
+async fn run_downloads(
+    invalid_files: Vec<FileToDownload>,
+    output: &mut futures::channel::mpsc::Sender<Message>,
+) -> Result<(), Error> {
+    let tmp_dir = Arc::new(
+        TempDir::new("golden-forgery")
+            .map_err(|_| Error::TmpDirCreateFail)?
+            .into_path(),
+    );
+
+    // Overall counters shared across every concurrent download so the single
+    // progress bar reflects aggregate bytes rather than per-file progress.
+    let downloaded = Arc::new(AtomicU64::new(0));
+
+    // Blobs already downloaded in a previous run (or shared between identical
+    // manifest paths) are linked into place from here instead of re-fetched.
+    let cache = Cache::discover()?;
+
+    // The denominator must be fixed before any byte is written: if `total` grew
+    // as downloads started in waves the percentage would jump backwards every
+    // time a file began. Resolve each file's Content-Length up front and sum
+    // them once. A single missing length makes the whole run indeterminate and
+    // falls back to a running byte count.
+    let sizes = content_lengths(&invalid_files).await;
+    let length_known = Arc::new(AtomicBool::new(sizes.iter().all(Option::is_some)));
+    let total = Arc::new(AtomicU64::new(sizes.iter().flatten().sum()));
+
+    let invalid_files = Arc::new(invalid_files);
+    let sizes = Arc::new(sizes);
+
+    let mut downloads = stream::iter(
+        invalid_files
+            .iter()
+            .cloned()
+            .zip(sizes.iter().cloned())
+            .map(|(file, expected_len)| {
+                let tmp_dir = Arc::clone(&tmp_dir);
+                let downloaded = Arc::clone(&downloaded);
+                let total = Arc::clone(&total);
+                let length_known = Arc::clone(&length_known);
+                let cache = cache.clone();
+                let mut output = output.clone();
+                async move {
+                    download_file(
+                        &file,
+                        expected_len,
+                        &tmp_dir,
+                        &cache,
+                        &downloaded,
+                        &total,
+                        &length_known,
+                        &mut output,
+                    )
+                    .await
+                }
+            }),
+    )
+    .buffer_unordered(NUMBER_OF_MAX_CONCURRENT_DOWNLOADS);
+
+    while let Some(result) = downloads.next().await {
+        result?;
+    }
+
+    // Don't trust the bytes we just wrote: recompute every hash and, on
+    // mismatch, re-fetch the file once before giving up.
+    let _ = output.send(Message::BeginVerify).await;
+    verify_downloads(&invalid_files, &tmp_dir, &cache, output).await?;
+
+    // Populate the cache with the freshly verified files so repeated updates
+    // and deduplicated paths are free on bandwidth next time.
+    for file in invalid_files.iter() {
+        cache.put(&file.hash, &file.path);
+    }
+
+    Ok(())
+}
 
-        if let Ok(existing_file_hash) = Hash::try_from(&path) {
-            // Hashes match, so we skip it
-            if existing_file_hash == manifest_hash {
-                continue;
+/// Recompute the on-disk hash of every file that was just fetched and compare
+/// it to the manifest's expected hash. A mismatch triggers a single re-download
+/// of that file; if it still doesn't match, fail with [`Error::HashMismatch`].
+async fn verify_downloads(
+    invalid_files: &[FileToDownload],
+    tmp_dir: &std::path::Path,
+    cache: &Cache,
+    output: &mut futures::channel::mpsc::Sender<Message>,
+) -> Result<(), Error> {
+    let mut checks = stream::iter(invalid_files.iter().map(|file| {
+        let mut output = output.clone();
+        async move {
+            if file_matches(file).await? {
+                return Ok(());
+            }
+
+            // Single retry: pull the file again, then verify once more.
+            let scratch = AtomicU64::new(0);
+            let total = AtomicU64::new(0);
+            let length_known = AtomicBool::new(true);
+            download_file(
+                file,
+                None,
+                tmp_dir,
+                cache,
+                &scratch,
+                &total,
+                &length_known,
+                &mut output,
+            )
+            .await?;
+
+            if file_matches(file).await? {
+                Ok(())
+            } else {
+                let got = Hash::of_file(&file.path, file.hash.algorithm())
+                    .map(|h| h.as_hex().to_string())
+                    .unwrap_or_default();
+                Err(Error::HashMismatch {
+                    path: file.path.clone(),
+                    expected: file.hash.as_hex().to_string(),
+                    got,
+                })
             }
-        } else {
-            // Couldn't calculate hash of existing file for whatever reason, so we download it
-            invalid_files.push(path);
         }
+    }))
+    .buffer_unordered(NUMBER_OF_MAX_CONCURRENT_HASHES);
+
+    while let Some(result) = checks.next().await {
+        result?;
     }
 
-    Ok(invalid_files)
+    Ok(())
 }
 
-async fn update(release: Release) -> Result<(), Error> {
-    // Download release
-    let release_zip_url = release
-        .assets
-        .iter()
-        .find(|asset| {
-            asset.content_type == "application/zip" && asset.name.starts_with("golden-forgery")
-        })
-        .map(|asset| asset.browser_download_url.clone())
-        .ok_or(Error::ReleaseZipNotFound)?;
+/// Whether the file on disk hashes to its expected manifest hash. Hashing runs
+/// on the blocking pool since it is CPU-bound.
+async fn file_matches(file: &FileToDownload) -> Result<bool, Error> {
+    let path = file.path.clone();
+    let expected = file.hash.clone();
+    let algorithm = expected.algorithm();
+    match tokio::task::spawn_blocking(move || Hash::of_file(&path, algorithm)).await {
+        Ok(Ok(actual)) => Ok(actual == expected),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(Error::GenericError),
+    }
+}
 
-    let tmp_dir = TempDir::new("golden-forgery")
-        .map_err(|_| Error::TmpDirCreateFail)?
-        .into_path();
-    let zip_bytes = reqwest::get(release_zip_url).await?.bytes().await?;
+/// Resolve the Content-Length of every file with a bounded set of HEAD requests
+/// so the overall total can be summed once before streaming starts. A file
+/// whose length can't be determined yields `None`, which forces the whole run
+/// into the indeterminate byte-count fallback.
+async fn content_lengths(files: &[FileToDownload]) -> Vec<Option<u64>> {
+    let client = reqwest::Client::new();
+    stream::iter(files.iter().map(|file| {
+        let client = client.clone();
+        async move {
+            client
+                .head(&file.url)
+                .send()
+                .await
+                .ok()
+                .and_then(|response| response.content_length())
+        }
+    }))
+    .buffered(NUMBER_OF_MAX_CONCURRENT_DOWNLOADS)
+    .collect()
+    .await
+}
 
-    let zip_path = tmp_dir.join("release.zip");
-    std::fs::write(&zip_path, zip_bytes).map_err(|_| Error::GenericError)?;
+/// Stream a single file to a temp path and move it into place, accumulating its
+/// bytes into the shared overall counter and emitting a progress `Message` per
+/// chunk. `expected_len` is the file's pre-summed Content-Length, used to
+/// advance the bar when a cached blob is linked in without streaming.
+async fn download_file(
+    file: &FileToDownload,
+    expected_len: Option<u64>,
+    tmp_dir: &std::path::Path,
+    cache: &Cache,
+    downloaded: &AtomicU64,
+    total: &AtomicU64,
+    length_known: &AtomicBool,
+    output: &mut futures::channel::mpsc::Sender<Message>,
+) -> Result<(), Error> {
+    // A blob we already have content-addressed is linked into place rather than
+    // re-fetched over the network. Its size still counts toward the bar so the
+    // aggregate reaches 100% even when nothing was streamed.
+    if let Some(cached) = cache.get(&file.hash).await {
+        place_file(&cached, &file.path)?;
+        if let Some(len) = expected_len {
+            let written = downloaded.fetch_add(len, Ordering::Relaxed) + len;
+            emit_progress(written, total, length_known, output).await;
+        }
+        return Ok(());
+    }
 
-    let file_reader = std::fs::File::open(&zip_path).map_err(|_| Error::GenericError)?;
-    let mut zip = zip::ZipArchive::new(file_reader).map_err(|_| Error::GenericError)?;
+    let response = reqwest::get(&file.url).await?;
 
-    println!("Extracting...");
-    zip.extract(".").map_err(|_| Error::GenericError)?;
+    let tmp_path = tmp_dir.join(file.hash.as_hex());
+    let mut writer = std::fs::File::create(&tmp_path).map_err(|_| Error::GenericError)?;
 
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        writer.write_all(&chunk).map_err(|_| Error::GenericError)?;
+        let written =
+            downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+        emit_progress(written, total, length_known, output).await;
+    }
+
+    writer.flush().map_err(|_| Error::GenericError)?;
+    drop(writer);
+
+    if let Some(parent) = file.path.parent() {
+        std::fs::create_dir_all(parent).map_err(|_| Error::GenericError)?;
+    }
+    std::fs::rename(&tmp_path, &file.path).map_err(|_| Error::GenericError)?;
+
+    Ok(())
+}
+
+/// Emit a progress `Message` from the shared counters. The total is fixed up
+/// front so the percentage is monotonic; without a known total the bar falls
+/// back to a running byte count.
+async fn emit_progress(
+    written: u64,
+    total: &AtomicU64,
+    length_known: &AtomicBool,
+    output: &mut futures::channel::mpsc::Sender<Message>,
+) {
+    let total = total.load(Ordering::Relaxed);
+    let progress = if length_known.load(Ordering::Relaxed) && total > 0 {
+        DownloadProgress::Determinate(written as f32 / total as f32 * 100.0)
+    } else {
+        DownloadProgress::Indeterminate(written)
+    };
+    let _ = output.send(Message::Progress(progress)).await;
+}
+
+/// Put `source` at `dest`, creating parent directories. A hardlink is preferred
+/// to avoid copying bytes, falling back to a copy across filesystem boundaries.
+fn place_file(source: &std::path::Path, dest: &std::path::Path) -> Result<(), Error> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|_| Error::GenericError)?;
+    }
+    let _ = std::fs::remove_file(dest);
+    if std::fs::hard_link(source, dest).is_err() {
+        std::fs::copy(source, dest).map_err(|_| Error::GenericError)?;
+    }
     Ok(())
 }
 
@@ -101,15 +378,31 @@ pub enum Error {
     #[error("Invalid hash in manifest file")]
     InvalidHash,
 
+    #[error("The manifest signature is missing or invalid. Refusing to update.")]
+    ManifestSignatureInvalid,
+
     #[error("Failed to read file")]
     FileReadError,
 
     #[error("Could not find the zip file in the latest release. Please contact the developers.")]
     ReleaseZipNotFound,
 
+    #[error("No download URL could be resolved for {0:?}")]
+    NoDownloadUrl(PathBuf),
+
+    #[error("Hash mismatch for {path:?}: expected {expected}, got {got}")]
+    HashMismatch {
+        path: PathBuf,
+        expected: String,
+        got: String,
+    },
+
     #[error("Could not create temporary directory")]
     TmpDirCreateFail,
 
+    #[error("Could not locate a per-user cache directory")]
+    CacheDirNotFound,
+
     #[error("GenericError")]
     GenericError,
 }
@@ -118,12 +411,25 @@ pub enum Error {
 struct Progress {
     value: f32,
     status: Status,
+    /// Overrides the status line while a download without a known length is in
+    /// flight, e.g. an indeterminate running byte count.
+    detail: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum DownloadProgress {
+    /// Percentage completed, known from Content-Length.
+    Determinate(f32),
+    /// Bytes written so far when Content-Length is unavailable.
+    Indeterminate(u64),
 }
 
 #[derive(Debug)]
 enum Message {
     BeginCompare(Release, Manifest),
-    BeginUpdate(Release, Vec<PathBuf>),
+    BeginUpdate(Release, Vec<FileToDownload>),
+    Progress(DownloadProgress),
+    BeginVerify,
     Finish,
     Err(String),
 }
@@ -133,7 +439,8 @@ enum Status {
     #[default]
     Checking,
     Comparing(Manifest),
-    Updating(Vec<PathBuf>),
+    Updating(Vec<FileToDownload>),
+    Verifying,
     Finished,
     Error(String),
 }
@@ -147,6 +454,7 @@ impl ToString for Status {
                 invalid_files.len()
             ),
             Status::Comparing(_manifest) => format!("Checking local files"),
+            Status::Verifying => "Verifying downloaded files...".into(),
             Status::Finished => "Updated! Launching Umineko: Golden Forgery".into(),
             Status::Error(err) => format!("Error: {err}"),
         }
@@ -161,7 +469,7 @@ impl Progress {
 
                 Task::perform(compare_files(manifest), |f| match f {
                     Ok(invalid_files) => {
-                        if invalid_files.len() > 0 {
+                        if !invalid_files.is_empty() {
                             Message::BeginUpdate(release, invalid_files)
                         } else {
                             Message::Finish
@@ -170,20 +478,36 @@ impl Progress {
                     Err(err) => Message::Err(err.to_string()),
                 })
             }
-            Message::BeginUpdate(release, invalid_files) => {
+            Message::BeginUpdate(_release, invalid_files) => {
                 self.status = Status::Updating(invalid_files.clone());
-                Task::perform(update(release), |f| match f {
-                    Ok(_) => Message::Finish,
-                    Err(err) => Message::Err(err.to_string()),
-                })
-                .chain(check_update_task())
+                self.value = 0.0;
+                Task::run(update(invalid_files), |message| message).chain(check_update_task())
+            }
+            Message::Progress(progress) => {
+                match progress {
+                    DownloadProgress::Determinate(value) => {
+                        self.value = value;
+                        self.detail = None;
+                    }
+                    DownloadProgress::Indeterminate(bytes) => {
+                        self.detail = Some(format!("Downloading... {bytes} bytes"));
+                    }
+                }
+                Task::none()
+            }
+            Message::BeginVerify => {
+                self.status = Status::Verifying;
+                self.detail = None;
+                Task::none()
             }
             Message::Finish => {
                 self.status = Status::Finished;
+                self.detail = None;
                 Task::none()
             }
             Message::Err(err) => {
                 self.status = Status::Error(err);
+                self.detail = None;
                 Task::none()
             }
         }
@@ -192,10 +516,15 @@ impl Progress {
     fn view(&self) -> Element<'_, Message> {
         let bar = progress_bar(0.0..=100.0, self.value);
 
+        let status_line = self
+            .detail
+            .clone()
+            .unwrap_or_else(|| self.status.to_string());
+
         column![center(
             column![
                 image("splash.png"),
-                center_x(text(self.status.to_string())),
+                center_x(text(status_line)),
                 bar,
             ]
             .spacing(20),
@@ -4,13 +4,38 @@ use std::str::FromStr;
 
 use crate::Error;
 use crate::hash::Hash;
+use ed25519_dalek::{Signature, VerifyingKey};
 use octocrab;
 use octocrab::models::repos::Release;
 use reqwest;
 
+/// Embedded ed25519 public key that every manifest is signed against.
+///
+/// TODO(release): THIS IS A PLACEHOLDER — all zeros, not a usable key.
+/// All-zero bytes decode to a small-order point, which `verify_strict`
+/// rejects as a weak key, so signature verification fails closed and no
+/// manifest will be accepted until a real project key is
+/// dropped in here. Generate the keypair offline, keep the private half off
+/// the build machines, and paste only the public half below before shipping.
+/// Do NOT substitute any published/test key (e.g. an RFC 8032 test vector):
+/// its secret is public, so anyone could forge a `manifest.sig`.
+const MANIFEST_PUBLIC_KEY: [u8; 32] = [0u8; 32];
+
 #[derive(Debug, Clone)]
 pub struct Manifest {
     pub files: HashMap<Hash, PathBuf>,
+    /// URL prefix from which an object can be fetched by its hash, i.e.
+    /// `<base_url><hash hex>` resolves to the blob for that manifest entry.
+    pub base_url: Option<String>,
+}
+
+/// A single file that `compare_files` found to be missing or out of date,
+/// resolved to the URL it should be fetched from.
+#[derive(Debug, Clone)]
+pub struct FileToDownload {
+    pub hash: Hash,
+    pub path: PathBuf,
+    pub url: String,
 }
 
 impl Manifest {
@@ -30,25 +55,121 @@ impl Manifest {
             .ok_or(Error::ManifestNotFound)?;
 
         let manifest_str = reqwest::get(manifest_url).await?.text().await?;
+
+        // The manifest is the trust root for the whole update chain, so it is
+        // only accepted once its detached signature verifies against the
+        // embedded public key. Missing signatures fail closed.
+        let signature_url = release
+            .assets
+            .iter()
+            .find(|asset| asset.name.to_lowercase() == "manifest.sig")
+            .map(|asset| asset.browser_download_url.clone())
+            .ok_or(Error::ManifestSignatureInvalid)?;
+
+        let signature = reqwest::get(signature_url).await?.bytes().await?;
+        verify_signature(manifest_str.as_bytes(), &signature)?;
+
         Ok((release, Self::try_from(manifest_str)?))
     }
+
+    /// Resolve the download URL for a manifest entry from the base URL prefix.
+    pub fn url_for(&self, hash: &Hash) -> Option<String> {
+        self.base_url
+            .as_ref()
+            .map(|base| format!("{base}{}", hash.as_hex()))
+    }
+}
+
+/// Verify a detached ed25519 signature over the manifest bytes against the
+/// embedded public key, failing closed with [`Error::ManifestSignatureInvalid`]
+/// on any problem.
+fn verify_signature(manifest: &[u8], signature: &[u8]) -> Result<(), Error> {
+    let verifying_key =
+        VerifyingKey::from_bytes(&MANIFEST_PUBLIC_KEY).map_err(|_| Error::ManifestSignatureInvalid)?;
+    let signature = Signature::from_slice(signature).map_err(|_| Error::ManifestSignatureInvalid)?;
+    verifying_key
+        .verify_strict(manifest, &signature)
+        .map_err(|_| Error::ManifestSignatureInvalid)
+}
+
+/// Whether a `match` expression selects the host we're running on. The
+/// expression is a space-separated list of `key=value` constraints over `os`
+/// and `arch`; every listed constraint must hold. An empty expression (or one
+/// using `any`/`*`) matches everything.
+fn variant_matches(expr: &str) -> bool {
+    expr.split_whitespace().all(|constraint| {
+        let Some((key, value)) = constraint.split_once('=') else {
+            return true;
+        };
+        if value == "any" || value == "*" {
+            return true;
+        }
+        match key {
+            "os" => value == std::env::consts::OS,
+            "arch" => value == std::env::consts::ARCH,
+            // Unknown keys don't constrain the host.
+            _ => true,
+        }
+    })
 }
 
 impl TryFrom<String> for Manifest {
     type Error = crate::Error;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let files = value
-            .lines()
-            .map(|line| {
-                line.split_once("  ").map(|(hash_str, path_str)| {
-                    (Hash::from_str(hash_str).unwrap(), PathBuf::from(path_str))
-                })
-            })
-            .filter(Option::is_some)
-            .map(Option::unwrap)
-            .collect::<HashMap<Hash, PathBuf>>();
-
-        Ok(Self { files })
+        let mut files = HashMap::new();
+        let mut base_url = None;
+
+        // Plain unqualified lines (the legacy format) sit in the always-active
+        // default variant; a `match` line opens a variant block whose following
+        // lines only apply when the host satisfies the expression. A variant
+        // block runs until the next blank line, which returns parsing to the
+        // default variant — so unqualified lines separated from a preceding
+        // block by a blank line keep the backward-compat guarantee and aren't
+        // silently swallowed.
+        let mut active = true;
+
+        for (index, line) in value.lines().enumerate() {
+            // Blank line: end of the current variant block, back to the default.
+            if line.trim().is_empty() {
+                active = true;
+                continue;
+            }
+
+            let Some((left, right)) = line.split_once("  ") else {
+                continue;
+            };
+
+            if left == "match" {
+                active = variant_matches(right);
+                continue;
+            }
+
+            if !active {
+                continue;
+            }
+
+            if left == "base_url" {
+                base_url = Some(right.to_string());
+                continue;
+            }
+
+            // A malformed digest neither panics nor aborts the parse: one typo
+            // must not brick every update. The offending line is skipped with a
+            // warning carrying its number so the manifest can still be fixed.
+            match Hash::from_str(left) {
+                Ok(hash) => {
+                    files.insert(hash, PathBuf::from(right));
+                }
+                Err(_) => {
+                    eprintln!(
+                        "warning: skipping manifest line {}: invalid digest {left:?}",
+                        index + 1
+                    );
+                }
+            }
+        }
+
+        Ok(Self { files, base_url })
     }
 }